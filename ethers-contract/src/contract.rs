@@ -0,0 +1,142 @@
+use crate::ContractError;
+
+use ethers_core::{abi::Abi, types::Address};
+use ethers_providers::JsonRpcClient;
+use ethers_signers::{Client, Signer};
+
+use std::future::Future;
+
+/// How many times to retry fetching the receipt for [`Contract::deployed`] before
+/// giving up and propagating the last error -- a still-pending tx looks the same
+/// as a transient RPC hiccup from here, so a single failed attempt isn't enough
+/// to conclude the contract was never deployed.
+const DEPLOYED_RECEIPT_RETRIES: usize = 3;
+
+#[derive(Debug, Clone)]
+/// A deployed or attached-to smart contract, ready for calls and events. The
+/// ABI and client are shared across all the contracts this is constructed from,
+/// only the `address` differs.
+pub struct Contract<'a, P, S> {
+    client: &'a Client<P, S>,
+    abi: &'a Abi,
+    address: Address,
+}
+
+impl<'a, P, S> Contract<'a, P, S>
+where
+    S: Signer,
+    P: JsonRpcClient,
+{
+    /// Creates a new contract at `address`, using the provided ABI and client
+    /// for any calls and transactions.
+    pub fn new(address: Address, abi: &'a Abi, client: &'a Client<P, S>) -> Self {
+        Self {
+            client,
+            abi,
+            address,
+        }
+    }
+
+    /// Returns a `Contract` at the address a past deployment transaction,
+    /// identified by `tx_hash`, created. This is the read-side counterpart to
+    /// [`Deployer::send`](crate::Deployer::send): it lets you reconnect to a
+    /// contract you (or someone else) deployed in a prior process without
+    /// having to persist its address yourself.
+    ///
+    /// Errors with [`ContractError::ContractNotDeployed`] if the receipt has no
+    /// contract address, i.e. the transaction did not create a contract. If no
+    /// receipt can be fetched within `DEPLOYED_RECEIPT_RETRIES + 1` attempts, which
+    /// is also what a still-pending transaction looks like, the last provider error
+    /// is returned instead of `ContractNotDeployed`.
+    pub async fn deployed(
+        abi: &'a Abi,
+        client: &'a Client<P, S>,
+        tx_hash: impl Into<ethers_core::types::TxHash>,
+    ) -> Result<Self, ContractError> {
+        // a still-pending tx has no receipt yet, which this crate's transports
+        // surface as an `Err`, not an empty `Ok`. Retry a few times in case it
+        // mines (or a transient RPC hiccup clears) in the meantime, but don't
+        // collapse a persistent, unrelated provider error into `ContractNotDeployed`
+        // -- propagate it once retries are exhausted.
+        let tx_hash = tx_hash.into();
+        let receipt = retry(
+            || client.get_transaction_receipt(tx_hash),
+            DEPLOYED_RECEIPT_RETRIES,
+        )
+        .await?;
+
+        let address = receipt
+            .contract_address
+            .ok_or(ContractError::ContractNotDeployed)?;
+
+        Ok(Self::new(address, abi, client))
+    }
+
+    /// Returns the contract's address
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Returns a reference to the contract's ABI
+    pub fn abi(&self) -> &Abi {
+        &self.abi
+    }
+
+    /// Returns a reference to the contract's client
+    pub fn client(&self) -> &Client<P, S> {
+        &self.client
+    }
+}
+
+/// Calls `attempt` up to `retries + 1` times, returning the first `Ok`, or the
+/// last `Err` if every attempt failed.
+async fn retry<F, Fut, T, E>(mut attempt: F, retries: usize) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut last_err = None;
+    for _ in 0..=retries {
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.expect("loop runs at least once"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[tokio::test]
+    async fn retry_succeeds_once_the_receipt_is_available() {
+        let calls = Cell::new(0);
+
+        let result: Result<u8, &str> = retry(
+            || {
+                let calls = &calls;
+                calls.set(calls.get() + 1);
+                async move {
+                    if calls.get() < 3 {
+                        Err("not mined yet")
+                    } else {
+                        Ok(42)
+                    }
+                }
+            },
+            DEPLOYED_RECEIPT_RETRIES,
+        )
+        .await;
+
+        assert_eq!(result, Ok(42));
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_propagates_the_last_error_once_exhausted_instead_of_swallowing_it() {
+        let result: Result<u8, &str> = retry(|| async { Err("unrelated rpc error") }, 2).await;
+        assert_eq!(result, Err("unrelated rpc error"));
+    }
+}
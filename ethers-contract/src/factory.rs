@@ -2,18 +2,37 @@ use crate::{Contract, ContractError};
 
 use ethers_core::{
     abi::{Abi, Tokenize},
-    types::{Bytes, TransactionRequest},
+    types::{Address, Bytes, TransactionRequest, H256, U256},
+    utils::keccak256,
 };
-use ethers_providers::JsonRpcClient;
+use ethers_providers::{JsonRpcClient, PubsubClient};
 use ethers_signers::{Client, Signer};
 
+use futures_util::stream::StreamExt;
 use std::time::Duration;
 use tokio::time;
 
-/// Poll for tx confirmation once every 7 seconds.
-// TODO: Can this be improved by replacing polling with an "on new block" subscription?
+/// Poll for tx confirmation once every 7 seconds. Only used as a fallback for
+/// transports that don't support subscriptions -- see [`Deployer::send_via_subscription`].
 const POLL_INTERVAL: u64 = 7000;
 
+/// Address of the canonical deterministic CREATE2 deployment proxy
+/// (<https://github.com/Arachnid/deterministic-deployment-proxy>), used as the
+/// default CREATE2 factory unless overridden with [`Deployer::create2_factory`].
+const DEFAULT_CREATE2_FACTORY: [u8; 20] = [
+    0x4e, 0x59, 0xb4, 0x48, 0x47, 0xb3, 0x79, 0x57, 0x85, 0x88, 0x92, 0x0c, 0xa7, 0x8f, 0xbf, 0x26,
+    0xc0, 0xb4, 0x95, 0x6c,
+];
+
+#[derive(Debug, Clone)]
+/// The CREATE2-specific parameters of a deployment, kept around so the
+/// predicted address can be computed instead of read off the receipt.
+struct Create2Deployment {
+    factory: Address,
+    salt: H256,
+    init_code: Bytes,
+}
+
 #[derive(Debug, Clone)]
 /// Helper which manages the deployment transaction of a smart contract
 pub struct Deployer<'a, P, S> {
@@ -22,6 +41,7 @@ pub struct Deployer<'a, P, S> {
     tx: TransactionRequest,
     confs: usize,
     poll_interval: Duration,
+    create2: Option<Create2Deployment>,
 }
 
 impl<'a, P, S> Deployer<'a, P, S>
@@ -42,25 +62,95 @@ where
         self
     }
 
+    /// Sets the `gas` to be used for the deployment transaction
+    pub fn gas<T: Into<U256>>(mut self, gas: T) -> Self {
+        self.tx.gas = Some(gas.into());
+        self
+    }
+
+    /// Sets the `gas_price` to be used for the deployment transaction
+    pub fn gas_price<T: Into<U256>>(mut self, gas_price: T) -> Self {
+        self.tx.gas_price = Some(gas_price.into());
+        self
+    }
+
+    /// Sets the `nonce` to be used for the deployment transaction
+    pub fn nonce<T: Into<U256>>(mut self, nonce: T) -> Self {
+        self.tx.nonce = Some(nonce.into());
+        self
+    }
+
+    /// Sets the `value` to be sent along with the deployment transaction, for
+    /// payable constructors
+    pub fn value<T: Into<U256>>(mut self, value: T) -> Self {
+        self.tx.value = Some(value.into());
+        self
+    }
+
+    /// Overrides the CREATE2 factory contract the deployment is sent to. Only
+    /// meaningful for deployments created via
+    /// [`ContractFactory::deploy_create2`]; a no-op otherwise.
+    pub fn create2_factory(mut self, factory: Address) -> Self {
+        if let Some(create2) = self.create2.as_mut() {
+            create2.factory = factory;
+            self.tx.to = Some(factory);
+        }
+        self
+    }
+
+    /// Returns the predicted address of the contract being deployed, without
+    /// broadcasting anything. Only meaningful for deployments created via
+    /// [`ContractFactory::deploy_create2`] -- `None` for regular deployments,
+    /// whose address depends on the sender's nonce.
+    pub fn compute_address(&self) -> Option<Address> {
+        self.create2
+            .as_ref()
+            .map(|create2| create2_address(create2.factory, create2.salt, &create2.init_code))
+    }
+
     /// Broadcasts the contract deployment transaction and after waiting for it to
     /// be sufficiently confirmed (default: 1), it returns a [`Contract`](./struct.Contract.html)
     /// struct at the deployed contract's address.
+    ///
+    /// Polls `eth_getTransactionReceipt` every [`poll_interval`](Self::poll_interval)
+    /// until the receipt is available, then polls the current block number at the
+    /// same interval until `confs` confirmations have elapsed. If the underlying transport supports
+    /// subscriptions, prefer [`send_via_subscription`](Self::send_via_subscription)
+    /// instead, which reacts to new blocks rather than polling on a timer.
     pub async fn send(self) -> Result<Contract<'a, P, S>, ContractError> {
         let tx_hash = self.client.send_transaction(self.tx, None).await?;
 
         // poll for the receipt
-        let address;
-        loop {
+        let receipt = loop {
             if let Ok(receipt) = self.client.get_transaction_receipt(tx_hash).await {
-                address = receipt
-                    .contract_address
-                    .ok_or(ContractError::ContractNotDeployed)?;
+                break receipt;
+            }
+
+            time::delay_for(self.poll_interval).await;
+        };
+        let mined = receipt
+            .block_number
+            .ok_or(ContractError::ContractNotDeployed)?
+            .as_u64();
+
+        // keep polling until `confs` confirmations have actually elapsed, rather than
+        // returning as soon as the receipt is merely present
+        loop {
+            let current = self.client.get_block_number().await?.as_u64();
+            if current.saturating_sub(mined) + 1 >= self.confs as u64 {
                 break;
             }
 
-            time::delay_for(Duration::from_millis(POLL_INTERVAL)).await;
+            time::delay_for(self.poll_interval).await;
         }
 
+        let address = match &self.create2 {
+            Some(create2) => create2_address(create2.factory, create2.salt, &create2.init_code),
+            None => receipt
+                .contract_address
+                .ok_or(ContractError::ContractNotDeployed)?,
+        };
+
         let contract = Contract::new(address, self.abi, self.client);
         Ok(contract)
     }
@@ -76,6 +166,50 @@ where
     }
 }
 
+impl<'a, P, S> Deployer<'a, P, S>
+where
+    S: Signer,
+    P: PubsubClient,
+{
+    /// Like [`send`](Self::send), but waits for confirmations by subscribing to new
+    /// block headers instead of polling `eth_getTransactionReceipt` on a timer. Only
+    /// available for transports that support subscriptions (e.g. WebSockets); use
+    /// [`send`](Self::send) over HTTP.
+    pub async fn send_via_subscription(self) -> Result<Contract<'a, P, S>, ContractError> {
+        let tx_hash = self.client.send_transaction(self.tx, None).await?;
+
+        let mut new_heads = self.client.subscribe_blocks().await?;
+        let receipt = loop {
+            let block = new_heads
+                .next()
+                .await
+                .ok_or(ContractError::ContractNotDeployed)?;
+
+            if let Ok(receipt) = self.client.get_transaction_receipt(tx_hash).await {
+                let current = block.number.ok_or(ContractError::ContractNotDeployed)?.as_u64();
+                let mined = receipt
+                    .block_number
+                    .ok_or(ContractError::ContractNotDeployed)?
+                    .as_u64();
+
+                if current.saturating_sub(mined) + 1 >= self.confs as u64 {
+                    break receipt;
+                }
+            }
+        };
+
+        let address = match &self.create2 {
+            Some(create2) => create2_address(create2.factory, create2.salt, &create2.init_code),
+            None => receipt
+                .contract_address
+                .ok_or(ContractError::ContractNotDeployed)?,
+        };
+
+        let contract = Contract::new(address, self.abi, self.client);
+        Ok(contract)
+    }
+}
+
 #[derive(Debug, Clone)]
 /// To deploy a contract to the Ethereum network, a `ContractFactory` can be
 /// created which manages the Contract bytecode and Application Binary Interface
@@ -122,7 +256,7 @@ where
 pub struct ContractFactory<'a, P, S> {
     client: &'a Client<P, S>,
     abi: &'a Abi,
-    bytecode: &'a Bytes,
+    bytecode: Bytes,
 }
 
 impl<'a, P, S> ContractFactory<'a, P, S>
@@ -137,10 +271,33 @@ where
         Self {
             client,
             abi,
-            bytecode,
+            bytecode: bytecode.clone(),
         }
     }
 
+    /// Links a library found in the bytecode at its linker placeholder with `address`.
+    ///
+    /// `name` may either be the library's bare name (e.g. `"SafeMath"`) or its
+    /// fully qualified name as emitted by the Solidity compiler (e.g.
+    /// `"contracts/Math.sol:SafeMath"`) -- whichever form was used to produce the
+    /// placeholder in the first place.
+    pub fn link(mut self, name: impl AsRef<str>, address: Address) -> Self {
+        let placeholder = link_placeholder(name.as_ref());
+        self.bytecode = replace_placeholder(&self.bytecode, &placeholder, address);
+        self
+    }
+
+    /// Links a library using its fully qualified `path:name`, e.g.
+    /// `link_fully_qualified("contracts/Math.sol", "SafeMath", address)`.
+    pub fn link_fully_qualified(
+        self,
+        path: impl AsRef<str>,
+        name: impl AsRef<str>,
+        address: Address,
+    ) -> Self {
+        self.link(format!("{}:{}", path.as_ref(), name.as_ref()), address)
+    }
+
     /// Constructs the deployment transaction based on the provided constructor
     /// arguments and returns a `Deployer` instance. You must call `send()` in order
     /// to actually deploy the contract.
@@ -153,6 +310,10 @@ where
         &self,
         constructor_args: T,
     ) -> Result<Deployer<'a, P, S>, ContractError> {
+        if has_unlinked_placeholder(&self.bytecode) {
+            return Err(ContractError::UnlinkedLibrary);
+        }
+
         // Encode the constructor args & concatenate with the bytecode if necessary
         let params = constructor_args.into_tokens();
         let data: Bytes = match (self.abi.constructor(), params.is_empty()) {
@@ -178,6 +339,121 @@ where
             tx,
             confs: 1,
             poll_interval: Duration::from_millis(POLL_INTERVAL),
+            create2: None,
         })
     }
+
+    /// Like [`deploy`](Self::deploy), but targets a CREATE2 factory contract with the
+    /// given `salt` so the resulting address is deterministic and independent of the
+    /// deployer's nonce. Defaults to the canonical deterministic deployment proxy;
+    /// override it with [`Deployer::create2_factory`] if a different factory is needed.
+    pub fn deploy_create2<T: Tokenize>(
+        &self,
+        constructor_args: T,
+        salt: H256,
+    ) -> Result<Deployer<'a, P, S>, ContractError> {
+        let mut deployer = self.deploy(constructor_args)?;
+
+        let init_code = deployer.tx.data.clone().unwrap_or_default();
+        let factory = Address::from_slice(&DEFAULT_CREATE2_FACTORY);
+
+        let mut calldata = salt.as_bytes().to_vec();
+        calldata.extend_from_slice(&init_code.0);
+
+        deployer.tx.to = Some(factory);
+        deployer.tx.data = Some(Bytes(calldata));
+        deployer.create2 = Some(Create2Deployment {
+            factory,
+            salt,
+            init_code,
+        });
+
+        Ok(deployer)
+    }
+}
+
+/// Computes the deterministic CREATE2 address for `init_code` deployed by `factory`
+/// with `salt`, per `keccak256(0xff ++ factory ++ salt ++ keccak256(init_code))[12..]`.
+fn create2_address(factory: Address, salt: H256, init_code: &Bytes) -> Address {
+    let init_code_hash = keccak256(&init_code.0);
+
+    let mut bytes = Vec::with_capacity(1 + 20 + 32 + 32);
+    bytes.push(0xff);
+    bytes.extend_from_slice(factory.as_bytes());
+    bytes.extend_from_slice(salt.as_bytes());
+    bytes.extend_from_slice(&init_code_hash);
+
+    Address::from_slice(&keccak256(&bytes)[12..])
+}
+
+/// Builds the `__$<34 hex chars>$__` placeholder solc emits for an unresolved
+/// library reference, keyed off the first 17 bytes of `keccak256(name)`.
+fn link_placeholder(name: &str) -> String {
+    let hash = keccak256(name.as_bytes());
+    format!("__${}$__", hex::encode(&hash[..17]))
+}
+
+/// Replaces every occurrence of `placeholder`'s raw ASCII bytes in `bytecode`
+/// with `address`'s 20 bytes. The placeholder's ASCII text, not its hex
+/// encoding, is what actually appears in the bytecode at an unresolved library
+/// reference, so the search has to happen on the raw bytes directly.
+fn replace_placeholder(bytecode: &Bytes, placeholder: &str, address: Address) -> Bytes {
+    let needle = placeholder.as_bytes();
+    let haystack = &bytecode.0;
+
+    let mut resolved = Vec::with_capacity(haystack.len());
+    let mut i = 0;
+    while i < haystack.len() {
+        if haystack[i..].starts_with(needle) {
+            resolved.extend_from_slice(address.as_bytes());
+            i += needle.len();
+        } else {
+            resolved.push(haystack[i]);
+            i += 1;
+        }
+    }
+
+    Bytes(resolved)
+}
+
+fn has_unlinked_placeholder(bytecode: &Bytes) -> bool {
+    bytecode.0.windows(3).any(|window| window == b"__$")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_placeholder_resolves_link_reference() {
+        let address = Address::from_slice(&[0x11; 20]);
+        let placeholder = link_placeholder("contracts/Math.sol:SafeMath");
+
+        assert!(placeholder.starts_with("__$"));
+        assert!(placeholder.ends_with("$__"));
+        assert_eq!(placeholder.len(), 40);
+
+        let mut raw = b"6080604052".to_vec();
+        raw.extend_from_slice(placeholder.as_bytes());
+        raw.extend_from_slice(b"6001600201");
+        let bytecode = Bytes(raw);
+
+        assert!(has_unlinked_placeholder(&bytecode));
+
+        let linked = replace_placeholder(&bytecode, &placeholder, address);
+        assert!(!has_unlinked_placeholder(&linked));
+        assert!(linked.0.windows(20).any(|window| window == address.as_bytes()));
+    }
+
+    #[test]
+    fn create2_address_matches_known_vector() {
+        // https://eips.ethereum.org/EIPS/eip-1014 example 1
+        let factory = Address::from_slice(&[0x00; 20]);
+        let salt = H256::zero();
+        let init_code = Bytes(vec![0x00]);
+
+        let expected =
+            Address::from_slice(&hex::decode("4D1A2e2bB4F88F0250f26Ffff098B0b30B26BF38").unwrap());
+        assert_eq!(create2_address(factory, salt, &init_code), expected);
+    }
 }
\ No newline at end of file